@@ -2,6 +2,8 @@
 #ifndef DEVSTORE_SDK_H
 #define DEVSTORE_SDK_H
 
+#include <stdbool.h>
+#include <stddef.h>
 #include <stdint.h>
 
 #ifdef __cplusplus
@@ -15,27 +17,202 @@ typedef enum DevstoreMessageStatus {
     DEVSTORE_MESSAGE_STATUS_ERROR = 3,
 } DevstoreMessageStatus;
 
+typedef enum DevstoreProgressPhase {
+    DEVSTORE_PROGRESS_PHASE_DOWNLOAD = 0,
+    DEVSTORE_PROGRESS_PHASE_EXTRACT = 1,
+} DevstoreProgressPhase;
+
+typedef enum DevstoreLoopStatus {
+    DEVSTORE_LOOP_STATUS_STOPPED = 0,
+    DEVSTORE_LOOP_STATUS_RUNNING = 1,
+    DEVSTORE_LOOP_STATUS_PAUSED = 2,
+} DevstoreLoopStatus;
+
+#define DEVSTORE_CODE_DISK_FULL 1001
+#define DEVSTORE_CODE_PERMISSION_DENIED 1002
+#define DEVSTORE_CODE_READ_ONLY_FILESYSTEM 1003
+#define DEVSTORE_CODE_PRECHECK_SIZE 1101
+#define DEVSTORE_CODE_PRECHECK_OFFLINE 1102
+#define DEVSTORE_CODE_PRECHECK_QUOTA 1103
+#define DEVSTORE_CODE_PRECHECK_CONFLICT 1104
+#define DEVSTORE_CODE_REQUEST_VETOED 1201
+#define DEVSTORE_CODE_SIGNATURE_INVALID 1301
+#define DEVSTORE_CODE_AUTH_INVALID 1401
+#define DEVSTORE_CODE_SERVER_ERROR 1402
+#define DEVSTORE_CODE_MALFORMED_RESPONSE 1403
+#define DEVSTORE_CODE_RESPONSE_TOO_LARGE 1404
+#define DEVSTORE_CODE_STATUS_MISCONFIGURED 1405
+#define DEVSTORE_CODE_NO_SAVE 1501
+#define DEVSTORE_CODE_SAVE_CONFLICT 1502
+#define DEVSTORE_CODE_NOT_MODIFIED 1503
+#define DEVSTORE_CODE_BUDGET_EXCEEDED 1601
+#define DEVSTORE_CODE_INVALID_PARAM 1701
+#define DEVSTORE_CODE_PROXY_UNREACHABLE 1801
+#define DEVSTORE_CODE_PROXY_AUTH_REQUIRED 1802
+
+#define DEVSTORE_PRECHECK_SIZE (1u << 0)
+#define DEVSTORE_PRECHECK_ONLINE (1u << 1)
+#define DEVSTORE_PRECHECK_QUOTA (1u << 2)
+#define DEVSTORE_PRECHECK_CONFLICT (1u << 3)
+
+#define DEVSTORE_COMPRESSION_DEFLATED 0
+#define DEVSTORE_COMPRESSION_STORED 1
+
+#define DEVSTORE_SYMLINK_REFUSE 0
+#define DEVSTORE_SYMLINK_RESOLVE 1
+
+#define DEVSTORE_ABSOLUTE_ENTRY_STRIP 0
+#define DEVSTORE_ABSOLUTE_ENTRY_REJECT 1
+
+#define DEVSTORE_NOTIFY_FALLBACK_NATIVE (1u << 0)
+#define DEVSTORE_NOTIFY_FALLBACK_CONSOLE (1u << 1)
+#define DEVSTORE_NOTIFY_FALLBACK_CALLBACK (1u << 2)
+#define DEVSTORE_NOTIFY_FALLBACK_FILE (1u << 3)
+
 typedef struct DevstoreFfiMessage {
     DevstoreMessageStatus status;
     uint32_t code;
     char* message;
 } DevstoreFfiMessage;
 
+typedef struct DevstoreUploadOptions {
+    uint32_t compression;
+    uint32_t prechecks;
+    uint64_t max_upload_size;
+    bool dedup;
+    bool transport_compression;
+} DevstoreUploadOptions;
+
+typedef const char* (*DevstoreNotificationStoreLoad)(void* user_data);
+typedef void (*DevstoreNotificationStoreSave)(void* user_data, const char* cache_json);
+typedef void (*DevstoreNotificationCallback)(void* user_data, const char* title, const char* body);
+typedef int (*DevstoreRequestHook)(const char* method, const char* url, void* user_data);
+// Callbacks returning char* hand the SDK a string made with devstore_alloc_string();
+// the SDK owns it from then on and frees it. Return NULL when there is nothing to give.
+typedef char* (*DevstoreTokenRefreshCallback)(void* user_data);
+typedef char* (*DevstorePrefPathProvider)(void);
+typedef void (*DevstorePanicCallback)(void* user_data, const char* report_json);
+typedef void (*DevstoreProgressCallback)(void* user_data, DevstoreProgressPhase phase, uint64_t done, uint64_t total);
+typedef void (*DevstoreVersionCallback)(void* user_data, const char* product_id, const char* version);
+typedef void (*DevstoreProductPollCallback)(void* user_data, const char* product_id, const DevstoreFfiMessage* result);
+
 DevstoreFfiMessage* get_sdk_version(void);
+DevstoreFfiMessage* get_capabilities(void);
+DevstoreFfiMessage* get_sdk_disk_usage(void);
+DevstoreFfiMessage* clear_sdk_state(const char* category);
 DevstoreFfiMessage* set_custom_url(const char* custom_url);
+DevstoreFfiMessage* set_custom_header(const char* name, const char* value);
+DevstoreFfiMessage* clear_custom_headers(void);
+DevstoreFfiMessage* set_pool_max_idle_per_host(uint32_t max_idle);
+DevstoreFfiMessage* set_http2_prior_knowledge(bool enabled);
+DevstoreFfiMessage* set_resolve_override(const char* host, const char* ip_port);
+DevstoreFfiMessage* clear_resolve_overrides(void);
+DevstoreFfiMessage* set_proxy(const char* url);
+DevstoreFfiMessage* test_proxy(void);
+DevstoreFfiMessage* add_root_certificate(const uint8_t* pem, size_t len);
+DevstoreFfiMessage* set_request_hook(DevstoreRequestHook hook, void* user_data);
+DevstoreFfiMessage* set_pref_path_provider(DevstorePrefPathProvider provider);
+DevstoreFfiMessage* set_token_refresh_callback(DevstoreTokenRefreshCallback callback, void* user_data);
 DevstoreFfiMessage* upload_save_to_server(const char* package_id, const char* user_secret, const char* file_or_folder_path);
+DevstoreFfiMessage* upload_save_to_server_streaming(const char* package_id, const char* user_secret, const char* file_path);
+DevstoreFfiMessage* set_upload_prechecks(uint32_t prechecks);
+DevstoreFfiMessage* set_max_upload_size(uint64_t max_bytes);
+DevstoreFfiMessage* set_progress_fd(int fd);
+DevstoreFfiMessage* set_temp_dir(const char* path);
+DevstoreFfiMessage* set_max_response_size(uint64_t max_bytes);
+DevstoreFfiMessage* set_max_download_size(uint64_t max_bytes);
+DevstoreFfiMessage* set_download_concurrency(uint32_t max_requests);
+DevstoreFfiMessage* set_extract_symlink_policy(uint32_t policy, const char* allowed_root);
+DevstoreFfiMessage* set_absolute_entry_policy(uint32_t policy);
+DevstoreFfiMessage* set_stream_chunk_bounds(uint32_t min_bytes, uint32_t max_bytes);
+DevstoreFfiMessage* set_preserve_timestamps(bool enabled);
+DevstoreFfiMessage* set_upload_compression(const char* method);
+DevstoreFfiMessage* set_product_option(const char* product_id, const char* key, const char* value);
+DevstoreFfiMessage* get_effective_product_config(const char* product_id);
+DevstoreFfiMessage* set_upload_dedup(bool enabled);
+DevstoreFfiMessage* set_transport_compression(bool enabled);
+DevstoreFfiMessage* set_upload_archive_name(const char* name);
+DevstoreFfiMessage* set_archive_format(const char* format);
+DevstoreFfiMessage* upload_save_to_server_checked(const char* package_id, const char* user_secret, const char* file_or_folder_path);
+DevstoreFfiMessage* queue_save_upload(const char* package_id, const char* file_or_folder_path);
+DevstoreFfiMessage* cancel_queued_upload(const char* queue_id);
+DevstoreFfiMessage* recover_pending_uploads(const char* user_secret);
+DevstoreFfiMessage* prune_upload_queue(uint32_t max_items, uint64_t max_age_seconds);
+DevstoreUploadOptions devstore_default_upload_options(void);
+DevstoreFfiMessage* upload_save_to_server_with_options(const char* package_id, const char* user_secret, const char* file_or_folder_path, const DevstoreUploadOptions* options);
+DevstoreFfiMessage* upload_paths_to_server(const char* package_id, const char* user_secret, const char* const* paths, size_t count);
+DevstoreFfiMessage* upload_save_to_server_include(const char* package_id, const char* user_secret, const char* base_path, const char* const* includes, size_t count);
+DevstoreFfiMessage* set_capture_last_response(bool enabled);
+DevstoreFfiMessage* get_last_raw_response(void);
+DevstoreFfiMessage* get_last_transfer_stats(void);
+DevstoreFfiMessage* get_network_usage(void);
+DevstoreFfiMessage* set_network_budget(uint64_t bytes);
+DevstoreFfiMessage* reset_network_usage(void);
+DevstoreFfiMessage* get_cloud_save_etag(const char* package_id, const char* user_secret);
+DevstoreFfiMessage* upload_save_to_server_if_match(const char* package_id, const char* user_secret, const char* file_or_folder_path, const char* etag);
 DevstoreFfiMessage* download_save_from_server(const char* package_id, const char* user_secret, const char* extract_path);
+DevstoreFfiMessage* download_save_if_newer(const char* package_id, const char* user_secret, const char* extract_path);
+DevstoreFfiMessage* restore_save(const char* package_id, const char* user_secret, const char* target_dir);
+DevstoreFfiMessage* download_save_streaming(const char* package_id, const char* user_secret, const char* extract_path, DevstoreProgressCallback progress_cb, void* user_data);
 DevstoreFfiMessage* get_version_from_id(const char* package_id);
+DevstoreFfiMessage* refresh_version_from_id(const char* package_id);
+DevstoreFfiMessage* set_version_cache_ttl(uint64_t seconds);
+DevstoreFfiMessage* fetch_app_icon(const char* product_id, const char* out_path);
 DevstoreFfiMessage* send_notification(const char* title, const char* body);
+DevstoreFfiMessage* test_notification(void);
+DevstoreFfiMessage* set_notification_log_file(const char* path);
+DevstoreFfiMessage* set_notification_fallback(uint32_t fallbacks, DevstoreNotificationCallback callback, void* user_data);
+DevstoreFfiMessage* set_loop_also_checks_versions(const char* const* product_ids, size_t count);
+DevstoreFfiMessage* set_version_available_callback(DevstoreVersionCallback callback, void* user_data);
 DevstoreFfiMessage* check_and_show_notification(const char* product_id);
+DevstoreFfiMessage* check_and_show_all_notifications(const char* const* product_ids, size_t count);
+DevstoreFfiMessage* set_notification_max_per_poll(uint32_t max);
+DevstoreFfiMessage* set_notification_min_gap_ms(uint32_t milliseconds);
+DevstoreFfiMessage* show_latest_notification_forced(const char* product_id);
 DevstoreFfiMessage* init_simple_loop(const char* product_id);
+DevstoreFfiMessage* init_registered_products_loop(void);
+DevstoreFfiMessage* register_product(const char* product_id);
+DevstoreFfiMessage* unregister_product(const char* product_id);
+DevstoreFfiMessage* set_product_poll_callback(DevstoreProductPollCallback callback, void* user_data);
+DevstoreFfiMessage* set_notification_reshow_interval(uint64_t seconds);
+DevstoreFfiMessage* reset_notifications(void);
+DevstoreFfiMessage* set_notification_loop_interval(uint64_t seconds);
+DevstoreFfiMessage* restore_notification_loop(void);
+DevstoreFfiMessage* pause_notification_loop(void);
+DevstoreFfiMessage* resume_notification_loop(void);
+DevstoreFfiMessage* notification_loop_status(void);
+DevstoreFfiMessage* set_notification_store_callbacks(DevstoreNotificationStoreLoad load, DevstoreNotificationStoreSave save, void* user_data);
+DevstoreFfiMessage* export_notification_cache(void);
+DevstoreFfiMessage* list_recent_notifications(uint32_t max);
+DevstoreFfiMessage* import_notification_cache(const char* json);
 DevstoreFfiMessage* is_devstore_online(void);
+DevstoreFfiMessage* wait_until_online(uint32_t timeout_seconds);
+// devstore_shutdown() and request_shutdown() are final for the loaded library:
+// uploads, update downloads and waits started afterwards fail as cancelled.
+// Unload and load the library again to use the SDK after a shutdown.
+DevstoreFfiMessage* devstore_shutdown(uint32_t timeout_ms);
+DevstoreFfiMessage* request_shutdown(void);
+DevstoreFfiMessage* get_server_time(void);
+DevstoreFfiMessage* run_diagnostics(void);
+DevstoreFfiMessage* selftest_cloud_save(const char* package_id, const char* user_secret);
+DevstoreFfiMessage* prune_cloud_saves(const char* user_secret, const char* product_id, uint32_t keep_count, bool force);
+DevstoreFfiMessage* store_user_secret(const char* user_secret);
+DevstoreFfiMessage* clear_user_secret(void);
 DevstoreFfiMessage* get_current_username(const char* user_secret);
+DevstoreFfiMessage* list_owned_products(const char* user_secret);
+DevstoreFfiMessage* set_update_public_key(const char* public_key_hex);
 DevstoreFfiMessage* download_update_for_product(const char* package_id);
+DevstoreFfiMessage* update_if_available(const char* package_id, const char* current_version_hex);
+DevstoreFfiMessage* get_update_info(const char* package_id, const char* current_version_hex);
+DevstoreFfiMessage* compute_save_manifest(const char* path);
+DevstoreFfiMessage* list_staged_update(const char* package_id, const char* install_dir);
+DevstoreFfiMessage* cancel_update_download(void);
+DevstoreFfiMessage* apply_update(const char* package_id, const char* install_dir);
 DevstoreFfiMessage* verify_download_v2(const char* package_id);
 DevstoreFfiMessage* verify_download_code(const char* product_id, const char* code);
 DevstoreFfiMessage* verify_resigned_install_token(const char* product_id, const char* install_token);
 DevstoreFfiMessage* verify_resigned_package_path(const char* product_id, const char* package_or_root_path);
+DevstoreFfiMessage* set_panic_reporter(DevstorePanicCallback callback, void* user_data, bool upload_reports);
 DevstoreFfiMessage* init_sdk_for_user(const char* product_id, const char* secret_code);
 DevstoreFfiMessage* start_oauth_device_flow(const char* product_id, const char* return_url);
 DevstoreFfiMessage* start_qr_device_flow(const char* product_id);
@@ -43,6 +220,7 @@ DevstoreFfiMessage* get_code_from_oauth(const char* callback_url);
 DevstoreFfiMessage* set_presence_for_user(const char* details);
 DevstoreFfiMessage* discord_heartbeat(void);
 DevstoreFfiMessage* discord_quit(void);
+char* devstore_alloc_string(const char* text);
 void devstore_free_message(DevstoreFfiMessage* message);
 
 #ifdef __cplusplus
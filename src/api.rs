@@ -0,0 +1,1823 @@
+//! Safe Rust interface to the Devstore SDK.
+//!
+//! Every call here returns a `Result` instead of a `DevstoreFfiMessage`, so Rust
+//! games can link the crate directly. The `extern "C"` exports are thin wrappers
+//! that turn these results back into messages.
+
+use crate::{
+    ARCHIVE_FORMAT_HEADER, ArchiveFormat, CHANGELOG_CACHE_FILE, ChangelogCacheEntry, CloudSave,
+    CloudSavesResponse, DEVSTORE_CODE_AUTH_INVALID, DEVSTORE_CODE_DISK_FULL,
+    DEVSTORE_CODE_INVALID_PARAM, DEVSTORE_CODE_MALFORMED_RESPONSE, DEVSTORE_CODE_NO_SAVE,
+    DEVSTORE_CODE_PERMISSION_DENIED, DEVSTORE_CODE_READ_ONLY_FILESYSTEM,
+    DEVSTORE_CODE_REQUEST_VETOED, DEVSTORE_CODE_RESPONSE_TOO_LARGE, DEVSTORE_CODE_SAVE_CONFLICT,
+    DEVSTORE_CODE_SERVER_ERROR, DEVSTORE_CODE_SIGNATURE_INVALID, DISCORD_CONNECT_TIMEOUT,
+    DISCORD_REQUEST_TIMEOUT, DOWNLOAD_CONCURRENCY, DOWNLOAD_PART_SIZE, DevstoreFfiMessage,
+    DevstoreMessageStatus, DevstoreProgressPhase, DevstoreUploadOptions, DiskFiles, DownloadStats,
+    IconCacheEntry, LAST_TRANSFER_STATS, MAX_DOWNLOAD_SIZE, MAX_RESPONSE_SIZE, OperationGuard,
+    OwnedProduct, PendingUpload, ProgressReader, SAVE_MARKERS_FILE, SHUTDOWN_POLL_INTERVAL,
+    STATUS_CHECK_MAX_REDIRECTS, SaveArchive, SaveInfoResponse, SaveMarker, SaveRoot,
+    ShutdownReader, TempFile, TransferStats, UploadSettings, VERSION_CACHE_TTL, VersionCacheEntry,
+    WAIT_ONLINE_INITIAL_DELAY, WAIT_ONLINE_MAX_DELAY, ZSTD_MAGIC, api_base_url,
+    build_default_client, build_save_archive, cache_max_age, cancelled_on_shutdown,
+    clear_pending_upload, client_builder, collect_save_entries, create_temp_file, emit_progress,
+    envelope_message, error_message, extract_save_atomically, format_error_chain, get_pref_path,
+    io_failure, limit_body, load_upload_journal, message_with_code, normalize_product_id,
+    parse_owned_products, progress_enabled, read_response_text, read_save_file,
+    record_network_usage, record_pending_upload, replace_with_save, response_too_large,
+    run_upload_prechecks, safe_relative_path, save_content_sha256, send_request, sha256_hex,
+    shutdown_requested, spool_to_temp_file, stream_single_file_zip, unique_root_name, unix_now,
+    upload_archive_name, upload_settings, validate_extract_path, verify_reassembled_archive,
+    verify_save_archive, version_is_newer,
+};
+use flate2::Compression;
+use flate2::read::GzEncoder;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Error returned by the safe API. The C exports report it with the status
+/// from [`DevstoreError::status`] and the code from [`DevstoreError::code`].
+#[derive(Debug)]
+pub enum DevstoreError {
+    MissingParam(&'static str),
+    InvalidParam(&'static str),
+    Network(reqwest::Error),
+    Http { status: u16, message: String },
+    Zip(zip::result::ZipError),
+    Io(io::Error),
+    Server { message: String },
+    Precheck { code: u32, message: String },
+    Response { code: u32, message: String },
+    Keystore { message: String },
+    Cancelled,
+    Vetoed { method: String, url: String },
+    SignatureInvalid,
+    NoSave,
+}
+
+impl DevstoreError {
+    /// Code carried in `DevstoreFfiMessage::code`: the HTTP status for `Http`,
+    /// `DEVSTORE_CODE_*` for actionable filesystem errors, failed prechecks,
+    /// classified server responses, vetoed requests, bad update signatures and
+    /// invalid parameters, and 0 otherwise.
+    pub fn code(&self) -> u32 {
+        match self {
+            DevstoreError::Http { status, .. } => *status as u32,
+            DevstoreError::Io(error) => match error.kind() {
+                io::ErrorKind::FileTooLarge => DEVSTORE_CODE_RESPONSE_TOO_LARGE,
+                io::ErrorKind::StorageFull => DEVSTORE_CODE_DISK_FULL,
+                io::ErrorKind::PermissionDenied => DEVSTORE_CODE_PERMISSION_DENIED,
+                io::ErrorKind::ReadOnlyFilesystem => DEVSTORE_CODE_READ_ONLY_FILESYSTEM,
+                _ => 0,
+            },
+            DevstoreError::Precheck { code, .. } | DevstoreError::Response { code, .. } => *code,
+            DevstoreError::Vetoed { .. } => DEVSTORE_CODE_REQUEST_VETOED,
+            DevstoreError::SignatureInvalid => DEVSTORE_CODE_SIGNATURE_INVALID,
+            DevstoreError::NoSave => DEVSTORE_CODE_NO_SAVE,
+            DevstoreError::InvalidParam(_) => DEVSTORE_CODE_INVALID_PARAM,
+            _ => 0,
+        }
+    }
+
+    /// `Cancelled` is reported as a warning and `NoSave` as info; everything
+    /// else is an error.
+    pub fn status(&self) -> DevstoreMessageStatus {
+        match self {
+            DevstoreError::Cancelled => DevstoreMessageStatus::Warning,
+            DevstoreError::NoSave => DevstoreMessageStatus::Info,
+            _ => DevstoreMessageStatus::Error,
+        }
+    }
+
+    pub(crate) fn into_message(self) -> *mut DevstoreFfiMessage {
+        message_with_code(self.status(), self.code(), self.to_string())
+    }
+}
+
+impl fmt::Display for DevstoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DevstoreError::MissingParam(name) => write!(f, "Missing {} parameter", name),
+            DevstoreError::InvalidParam(name) => write!(f, "Invalid {} parameter", name),
+            DevstoreError::Network(error) => {
+                write!(f, "Error: Network error: {}", format_error_chain(error))
+            }
+            DevstoreError::Zip(error) => write!(f, "Error: Invalid zip archive: {}", error),
+            DevstoreError::Io(error) => write!(f, "{}", error),
+            DevstoreError::Http { message, .. }
+            | DevstoreError::Server { message }
+            | DevstoreError::Precheck { message, .. }
+            | DevstoreError::Response { message, .. }
+            | DevstoreError::Keystore { message } => f.write_str(message),
+            DevstoreError::Cancelled => f.write_str("Cancelled by SDK shutdown"),
+            DevstoreError::Vetoed { method, url } => {
+                write!(f, "Error: Request vetoed by hook: {} {}", method, url)
+            }
+            DevstoreError::SignatureInvalid => {
+                f.write_str("Error: Update signature verification failed; refusing to extract")
+            }
+            DevstoreError::NoSave => f.write_str("No cloud save exists for this product yet"),
+        }
+    }
+}
+
+impl std::error::Error for DevstoreError {}
+
+impl From<reqwest::Error> for DevstoreError {
+    fn from(error: reqwest::Error) -> Self {
+        DevstoreError::Network(error)
+    }
+}
+
+impl From<zip::result::ZipError> for DevstoreError {
+    fn from(error: zip::result::ZipError) -> Self {
+        DevstoreError::Zip(error)
+    }
+}
+
+/// Result of a successful save download.
+#[derive(Clone, Debug, Serialize)]
+pub struct SaveDownloadSummary {
+    /// Files the save holds, all written to `extract_path`.
+    pub files: u64,
+    /// Their total size in bytes.
+    pub bytes: u64,
+    pub extract_path: PathBuf,
+}
+
+/// Result of a successful save upload.
+#[derive(Clone, Debug)]
+pub struct UploadReceipt {
+    /// Message returned by the server, or its raw body when it sent no `message`.
+    pub message: String,
+    /// Hex SHA-256 of the archive that was sent.
+    pub sha256: String,
+    /// Size and timing of the transfer, also kept for `get_last_transfer_stats`.
+    pub stats: TransferStats,
+    /// ETag of the new cloud save, when the server sent one.
+    pub etag: Option<String>,
+}
+
+/// What [`recover_pending_uploads`] did with one upload journal entry.
+#[derive(Clone, Debug, Serialize)]
+pub struct RecoveredUpload {
+    /// The entry's queue id, which is also its original idempotency key.
+    pub queue_id: String,
+    pub product_id: String,
+    pub path: PathBuf,
+    /// "uploaded", "dropped" when its file or folder is gone, or "failed".
+    pub outcome: &'static str,
+    /// Why it failed; the entry stays journaled for the next attempt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// `DevstoreFfiMessage::code` of the failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u32>,
+}
+
+/// Precondition for [`upload_save_if`], so an upload only replaces the cloud
+/// save the caller last saw.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveCondition<'a> {
+    /// The cloud save must still have this ETag (`If-Match`).
+    Matches(&'a str),
+    /// There must be no cloud save yet (`If-None-Match: *`).
+    Absent,
+}
+
+/// Outcome of [`prune_cloud_saves`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub removed: usize,
+    pub bytes_freed: u64,
+}
+
+/// One file of a local save as [`save_manifest`] lists it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SaveManifestEntry {
+    /// Name the file gets inside an upload archive.
+    pub path: String,
+    pub size: u64,
+    /// Hex SHA-256 of the file's contents.
+    pub sha256: String,
+}
+
+/// The version check and, when an update is out, what it contains.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UpdateInfo {
+    pub update_available: bool,
+    pub latest_version: Value,
+    /// The published changelog, when the store has one for the update.
+    pub changelog: Option<String>,
+    /// Size of the patch in bytes, when the store reports it.
+    pub size: Option<u64>,
+}
+
+/// What the status endpoint answered, after following redirects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnlineStatus {
+    /// Any 2xx.
+    Online,
+    /// 503.
+    Maintenance,
+    /// A 4xx, which usually means the API URL or custom headers are wrong
+    /// rather than the store being down.
+    Misconfigured(u16),
+    /// Any other 5xx, or a redirect that could not be followed.
+    Unexpected(u16),
+}
+
+impl OnlineStatus {
+    pub fn http_status(&self) -> u16 {
+        match self {
+            OnlineStatus::Online => 200,
+            OnlineStatus::Maintenance => 503,
+            OnlineStatus::Misconfigured(status) | OnlineStatus::Unexpected(status) => *status,
+        }
+    }
+}
+
+pub(crate) enum VersionLookup {
+    Found(Value),
+    Unrecognized(String),
+}
+
+fn require<'a>(value: &'a str, name: &'static str) -> Result<&'a str, DevstoreError> {
+    if value.is_empty() {
+        return Err(DevstoreError::InvalidParam(name));
+    }
+    Ok(value)
+}
+
+fn server_error(message: impl Into<String>) -> DevstoreError {
+    DevstoreError::Server {
+        message: message.into(),
+    }
+}
+
+fn save_metadata(path: &Path) -> Result<Metadata, DevstoreError> {
+    fs::metadata(path).map_err(|e| {
+        io_failure(
+            &format!("Error: File or folder does not exist: {}", path.display()),
+            e,
+        )
+    })
+}
+
+fn http_error(response: reqwest::blocking::Response, label: &str) -> DevstoreError {
+    let status = response.status().as_u16();
+    let text = error_message(response).unwrap_or_else(|| "No response message".to_string());
+    DevstoreError::Http {
+        status,
+        message: format!("{} (status {}): {}", label, status, text),
+    }
+}
+
+/// Reads the body of a successful response; any other status becomes `Http`.
+fn response_text(
+    response: reqwest::blocking::Response,
+    label: &str,
+) -> Result<String, DevstoreError> {
+    if !response.status().is_success() {
+        return Err(http_error(response, label));
+    }
+    read_response_text(response)
+}
+
+/// Attaches `form` as a gzip-compressed body with `Content-Encoding: gzip`.
+fn gzip_multipart(
+    request: reqwest::blocking::RequestBuilder,
+    form: reqwest::blocking::multipart::Form,
+) -> Result<reqwest::blocking::RequestBuilder, DevstoreError> {
+    let content_type = format!("multipart/form-data; boundary={}", form.boundary());
+    let mut body = Vec::new();
+    GzEncoder::new(form.into_reader(), Compression::default())
+        .read_to_end(&mut body)
+        .map_err(|e| io_failure("Error: Failed to compress upload", e))?;
+    Ok(request
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .header(reqwest::header::CONTENT_ENCODING, "gzip")
+        .body(body))
+}
+
+pub(crate) fn upload_archive(
+    package_id: &str,
+    user_secret: &str,
+    archive: SaveArchive,
+) -> Result<UploadReceipt, DevstoreError> {
+    let settings = upload_settings(package_id);
+    upload_archive_if(package_id, user_secret, archive, &settings, None, None)
+}
+
+fn upload_archive_if(
+    package_id: &str,
+    user_secret: &str,
+    archive: SaveArchive,
+    settings: &UploadSettings,
+    idempotency_key: Option<&str>,
+    condition: Option<SaveCondition>,
+) -> Result<UploadReceipt, DevstoreError> {
+    verify_save_archive(&archive.data)?;
+    let _operation = OperationGuard::new("upload");
+    let SaveArchive {
+        data,
+        sha256,
+        build_time,
+        format,
+    } = archive;
+    let bytes_sent = data.len() as u64;
+    // Read through `ShutdownReader` so a shutdown stops the body midway.
+    let body = ShutdownReader(io::Cursor::new(data));
+    let part = if progress_enabled() {
+        let reader = ProgressReader::new(body, "upload", bytes_sent);
+        reqwest::blocking::multipart::Part::reader_with_length(reader, bytes_sent)
+    } else {
+        reqwest::blocking::multipart::Part::reader_with_length(body, bytes_sent)
+    };
+    let part = part
+        .file_name(upload_archive_name(format))
+        .mime_str(format.mime_type())?;
+    let form = reqwest::blocking::multipart::Form::new()
+        .text("user_secret", user_secret.to_string())
+        .text("product_id", package_id.to_string())
+        .part("save_file", part);
+
+    let client = build_default_client()?;
+    let mut request = client
+        .post(format!("{}cloud-saves/", api_base_url()))
+        .header(crate::ARCHIVE_SHA256_HEADER, sha256.as_str())
+        .header(ARCHIVE_FORMAT_HEADER, format.name());
+    if let Some(key) = idempotency_key {
+        request = request.header(crate::IDEMPOTENCY_KEY_HEADER, key);
+    }
+    request = match condition {
+        Some(SaveCondition::Matches(etag)) => request.header(reqwest::header::IF_MATCH, etag),
+        Some(SaveCondition::Absent) => request.header(reqwest::header::IF_NONE_MATCH, "*"),
+        None => request,
+    };
+    let request = if settings.transport_compression {
+        cancelled_on_shutdown(gzip_multipart(request, form))?
+    } else {
+        request.multipart(form)
+    };
+    let started = Instant::now();
+    let response = cancelled_on_shutdown(send_request(request))?;
+    upload_receipt(response, sha256, bytes_sent, build_time, started)
+}
+
+/// Turns the server's answer to a save upload into a receipt, recording the
+/// transfer stats. A 412 means the upload's condition no longer held.
+fn upload_receipt(
+    response: reqwest::blocking::Response,
+    sha256: String,
+    bytes_sent: u64,
+    build_time: Duration,
+    started: Instant,
+) -> Result<UploadReceipt, DevstoreError> {
+    if response.status() == reqwest::StatusCode::PRECONDITION_FAILED {
+        let text = error_message(response).unwrap_or_else(|| "No response message".to_string());
+        return Err(classified(
+            DEVSTORE_CODE_SAVE_CONFLICT,
+            format!(
+                "Error: The cloud save was changed by another device: {}",
+                text
+            ),
+        ));
+    }
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let text = response_text(response, "Upload failed")?;
+    let stats = TransferStats::new(bytes_sent, build_time, started.elapsed());
+    *LAST_TRANSFER_STATS.write().unwrap() = Some(stats);
+    let message = match serde_json::from_str::<Value>(&text) {
+        Ok(json) => match json.get("message") {
+            Some(Value::String(msg)) => msg.clone(),
+            Some(msg) => msg.to_string(),
+            None => text,
+        },
+        Err(_) => text,
+    };
+    Ok(UploadReceipt {
+        message,
+        sha256,
+        stats,
+        etag,
+    })
+}
+
+/// Zips a save file or folder and uploads it as the product's cloud save.
+pub fn upload_save(
+    package_id: &str,
+    user_secret: &str,
+    path: impl AsRef<Path>,
+) -> Result<UploadReceipt, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    let path = path.as_ref();
+    let metadata = save_metadata(path)?;
+    let settings = upload_settings(package_id);
+    upload_journaled(package_id, user_secret, path, metadata, &settings, None)
+}
+
+/// Uploads the single file `path` as the product's cloud save, zipping it on
+/// the fly into the request body so neither the file nor the archive is held
+/// in memory. The archive is always a zip, and transport compression, the
+/// SHA-256 header and the upload journal are skipped since they need the
+/// finished archive; the receipt carries the digest computed along the way.
+/// A read failure partway through aborts the request.
+pub fn upload_save_streaming(
+    package_id: &str,
+    user_secret: &str,
+    path: impl AsRef<Path>,
+) -> Result<UploadReceipt, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    let path = path.as_ref();
+    if !save_metadata(path)?.is_file() {
+        return Err(DevstoreError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Error: Streaming uploads take a single file",
+        )));
+    }
+    let settings = upload_settings(package_id);
+    let _operation = OperationGuard::new("upload");
+
+    let (reader, worker) = stream_single_file_zip(path, settings.compression);
+    let part = reqwest::blocking::multipart::Part::reader(reader)
+        .file_name(upload_archive_name(ArchiveFormat::Zip))
+        .mime_str(ArchiveFormat::Zip.mime_type())?;
+    let form = reqwest::blocking::multipart::Form::new()
+        .text("user_secret", user_secret.to_string())
+        .text("product_id", package_id.to_string())
+        .part("save_file", part);
+    let request = build_default_client()?
+        .post(format!("{}cloud-saves/", api_base_url()))
+        .header(ARCHIVE_FORMAT_HEADER, ArchiveFormat::Zip.name())
+        .multipart(form);
+    let started = Instant::now();
+    let response = send_request(request);
+    let streamed = worker.join().unwrap_or_else(|_| {
+        Err(DevstoreError::Io(io::Error::other(
+            "Error: Upload stream stopped unexpectedly",
+        )))
+    });
+    let (response, streamed) = match (response, streamed) {
+        (Ok(response), Ok(streamed)) => (response, streamed),
+        // The archive side failing is the cause; the request error only says
+        // the body stopped.
+        (_, Err(err)) if !is_broken_pipe(&err) => return cancelled_on_shutdown(Err(err)),
+        (Err(err), _) => return cancelled_on_shutdown(Err(err)),
+        (Ok(_), Err(err)) => return Err(err),
+    };
+    // The body had no known length, so send_request could not count it.
+    record_network_usage(streamed.size, 0);
+    upload_receipt(
+        response,
+        streamed.sha256,
+        streamed.size,
+        streamed.build_time,
+        started,
+    )
+}
+
+fn is_broken_pipe(err: &DevstoreError) -> bool {
+    matches!(err, DevstoreError::Io(error) if error.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Zips `path` and uploads it, keeping it in the upload journal under the pref
+/// path until the server confirms it, so [`recover_pending_uploads`] can retry
+/// it after a crash. A `retry` of a save whose contents are unchanged reuses
+/// its idempotency key; a changed save gets a fresh one, so the server does
+/// not take the new contents for the upload it already stored.
+pub(crate) fn upload_journaled(
+    package_id: &str,
+    user_secret: &str,
+    path: &Path,
+    metadata: Metadata,
+    settings: &UploadSettings,
+    retry: Option<&PendingUpload>,
+) -> Result<UploadReceipt, DevstoreError> {
+    let root = SaveRoot {
+        path,
+        metadata,
+        prefix: None,
+    };
+    let content_sha256 = save_content_sha256(&root)?;
+    let idempotency_key = retry
+        .filter(|pending| pending.sha256 == content_sha256)
+        .map(|pending| pending.idempotency_key.clone());
+    let archive = build_save_archive(&[root], settings)?;
+    let entry = PendingUpload::new(package_id, path, &content_sha256, idempotency_key);
+    record_pending_upload(&entry);
+    let key = Some(entry.idempotency_key.as_str());
+    let receipt = upload_archive_if(package_id, user_secret, archive, settings, key, None)?;
+    clear_pending_upload(&entry.idempotency_key);
+    Ok(receipt)
+}
+
+/// Adds `path` to the upload journal without uploading it, for
+/// [`recover_pending_uploads`] to send later, and returns the entry's id for
+/// `cancel_queued_upload`. The id doubles as the upload's idempotency key.
+/// Queueing a save that is already pending replaces its entry. Only a digest
+/// of the save's contents is taken now; it is zipped when it is sent.
+pub fn queue_upload(package_id: &str, path: impl AsRef<Path>) -> Result<String, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let path = path.as_ref();
+    let metadata = save_metadata(path)?;
+    let content_sha256 = save_content_sha256(&SaveRoot {
+        path,
+        metadata,
+        prefix: None,
+    })?;
+    let entry = PendingUpload::new(package_id, path, &content_sha256, None);
+    record_pending_upload(&entry);
+    Ok(entry.idempotency_key)
+}
+
+/// Uploads again every save left in the upload journal by a run that stopped
+/// mid-upload, reusing its idempotency key while the save is unchanged.
+/// Entries whose file or folder is gone are dropped. An entry that fails
+/// stays in the journal for the next attempt and the others are still tried,
+/// until the SDK shuts down. Returns what happened to each entry.
+pub fn recover_pending_uploads(user_secret: &str) -> Result<Vec<RecoveredUpload>, DevstoreError> {
+    let user_secret = require(user_secret, "user_secret")?;
+    let mut results = Vec::new();
+    for entry in load_upload_journal() {
+        let mut result = RecoveredUpload {
+            queue_id: entry.idempotency_key.clone(),
+            product_id: entry.product_id.clone(),
+            path: entry.path.clone(),
+            outcome: "uploaded",
+            error: None,
+            code: None,
+        };
+        if !entry.path.exists() {
+            clear_pending_upload(&entry.idempotency_key);
+            result.outcome = "dropped";
+            results.push(result);
+            continue;
+        }
+        let uploaded = save_metadata(&entry.path).and_then(|metadata| {
+            let settings = upload_settings(&entry.product_id);
+            upload_journaled(
+                &entry.product_id,
+                user_secret,
+                &entry.path,
+                metadata,
+                &settings,
+                Some(&entry),
+            )
+        });
+        let cancelled = matches!(uploaded, Err(DevstoreError::Cancelled));
+        if let Err(err) = uploaded {
+            result.outcome = "failed";
+            result.code = Some(err.code());
+            result.error = Some(err.to_string());
+        }
+        results.push(result);
+        if cancelled {
+            break;
+        }
+    }
+    Ok(results)
+}
+
+/// [`upload_save`] that only replaces the cloud save when `condition` holds.
+/// Otherwise the server answers 412 and this fails with
+/// `DEVSTORE_CODE_SAVE_CONFLICT`, leaving the cloud save untouched.
+pub fn upload_save_if(
+    package_id: &str,
+    user_secret: &str,
+    path: impl AsRef<Path>,
+    condition: SaveCondition,
+) -> Result<UploadReceipt, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    if let SaveCondition::Matches(etag) = condition {
+        require(etag, "etag")?;
+    }
+    let path = path.as_ref();
+    let metadata = save_metadata(path)?;
+
+    let settings = upload_settings(package_id);
+    let archive = build_save_archive(
+        &[SaveRoot {
+            path,
+            metadata,
+            prefix: None,
+        }],
+        &settings,
+    )?;
+    upload_archive_if(
+        package_id,
+        user_secret,
+        archive,
+        &settings,
+        None,
+        Some(condition),
+    )
+}
+
+/// ETag of the product's cloud save, for [`upload_save_if`]. Fails with
+/// [`DevstoreError::NoSave`] when there is no save yet.
+pub fn get_save_etag(package_id: &str, user_secret: &str) -> Result<String, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+
+    fetch_save_marker(package_id, user_secret)?
+        .etag
+        .ok_or_else(|| {
+            classified(
+                DEVSTORE_CODE_MALFORMED_RESPONSE,
+                "Error: Cloud save info has no ETag",
+            )
+        })
+}
+
+/// The ETag and `updated_at` of the product's cloud save, from its info.
+fn fetch_save_marker(package_id: &str, user_secret: &str) -> Result<SaveMarker, DevstoreError> {
+    let client = build_default_client()?;
+    let response = send_request(
+        client
+            .get(format!("{}cloud-saves/info/", api_base_url()))
+            .query(&[("user_secret", user_secret), ("product_id", package_id)]),
+    )?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND || status == reqwest::StatusCode::NO_CONTENT {
+        return Err(DevstoreError::NoSave);
+    }
+    if !status.is_success() {
+        return Err(http_error(response, "Error: Request failed"));
+    }
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let updated_at = read_response_text(response)
+        .ok()
+        .and_then(|text| serde_json::from_str::<SaveInfoResponse>(&text).ok())
+        .and_then(|info| info.updated_at);
+    Ok(SaveMarker { etag, updated_at })
+}
+
+/// [`download_save`] that first compares the cloud save's ETag and
+/// `updated_at` with the marker this function recorded for `extract_path`
+/// after its last download, and skips the download when they match and the
+/// folder is still there. Returns `None` when it skipped the download. A save
+/// whose info has neither field is always downloaded.
+pub fn download_save_if_newer(
+    package_id: &str,
+    user_secret: &str,
+    extract_path: impl AsRef<Path>,
+) -> Result<Option<SaveDownloadSummary>, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    let extract_path = validate_extract_path(extract_path.as_ref())?;
+
+    let current = fetch_save_marker(package_id, user_secret)?;
+    let known = current.etag.is_some() || current.updated_at.is_some();
+    let markers_path = get_pref_path().join(SAVE_MARKERS_FILE);
+    let load_markers = || -> BTreeMap<String, SaveMarker> {
+        fs::read(&markers_path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    };
+    let key = |path: &Path| {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        format!("{}|{}", package_id, path.display())
+    };
+    if known && extract_path.is_dir() && load_markers().get(&key(&extract_path)) == Some(&current) {
+        return Ok(None);
+    }
+
+    let summary = download_save(package_id, user_secret, &extract_path)?;
+    let mut markers = load_markers();
+    markers.insert(key(&extract_path), current);
+    if let Ok(data) = serde_json::to_vec_pretty(&markers) {
+        let _ = fs::write(&markers_path, data);
+    }
+    Ok(Some(summary))
+}
+
+/// Same as [`upload_save`], but runs the prechecks enabled with
+/// `set_upload_prechecks` first. A failing precheck is reported as
+/// [`DevstoreError::Precheck`] with its `DEVSTORE_CODE_PRECHECK_*` code.
+pub fn upload_save_checked(
+    package_id: &str,
+    user_secret: &str,
+    path: impl AsRef<Path>,
+) -> Result<UploadReceipt, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    let path = path.as_ref();
+    let metadata = save_metadata(path)?;
+
+    let settings = upload_settings(package_id);
+    run_upload_prechecks(package_id, user_secret, path, &settings)?;
+    upload_journaled(package_id, user_secret, path, metadata, &settings, None)
+}
+
+/// [`upload_save_checked`] with `options` in place of the global and
+/// per-product upload settings, for this call only.
+pub fn upload_save_with_options(
+    package_id: &str,
+    user_secret: &str,
+    path: impl AsRef<Path>,
+    options: &DevstoreUploadOptions,
+) -> Result<UploadReceipt, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    let settings = options.settings()?;
+    let path = path.as_ref();
+    let metadata = save_metadata(path)?;
+    run_upload_prechecks(package_id, user_secret, path, &settings)?;
+    upload_journaled(package_id, user_secret, path, metadata, &settings, None)
+}
+
+/// Lists the files an upload of `path` would contain, by archive name, with
+/// their sizes and SHA-256 hashes, so a save can be compared with another
+/// without uploading it.
+pub fn save_manifest(path: impl AsRef<Path>) -> Result<Vec<SaveManifestEntry>, DevstoreError> {
+    let path = path.as_ref();
+    let metadata = save_metadata(path)?;
+    let root = SaveRoot {
+        path,
+        metadata,
+        prefix: None,
+    };
+    let mut manifest = collect_save_entries(&root)?
+        .into_iter()
+        .map(|(name, file)| {
+            let data = read_save_file(&DiskFiles, &file)?;
+            Ok(SaveManifestEntry {
+                path: name,
+                size: data.len() as u64,
+                sha256: sha256_hex(&data),
+            })
+        })
+        .collect::<Result<Vec<_>, DevstoreError>>()?;
+    manifest.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(manifest)
+}
+
+/// Uploads several files or folders as one archive, each under a folder named
+/// after its basename.
+pub fn upload_paths<P>(
+    package_id: &str,
+    user_secret: &str,
+    paths: &[P],
+) -> Result<UploadReceipt, DevstoreError>
+where
+    P: AsRef<Path>,
+{
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    if paths.is_empty() {
+        return Err(DevstoreError::InvalidParam("paths"));
+    }
+
+    let mut used_names = HashSet::new();
+    let mut roots = Vec::with_capacity(paths.len());
+    for path in paths {
+        let path = path.as_ref();
+        let metadata = save_metadata(path)?;
+        let prefix = unique_root_name(path, metadata.is_dir(), &mut used_names);
+        roots.push(SaveRoot {
+            path,
+            metadata,
+            prefix: Some(prefix),
+        });
+    }
+
+    let archive = build_save_archive(&roots, &upload_settings(package_id))?;
+    upload_archive(package_id, user_secret, archive)
+}
+
+/// Uploads only the listed paths under `base_path`, keeping their place in the
+/// folder so a download restores them in position. Every include must exist.
+pub fn upload_included<P>(
+    package_id: &str,
+    user_secret: &str,
+    base_path: impl AsRef<Path>,
+    includes: &[P],
+) -> Result<UploadReceipt, DevstoreError>
+where
+    P: AsRef<Path>,
+{
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    let settings = upload_settings(package_id);
+    let archive = build_included_archive(&settings, base_path.as_ref(), includes)?;
+    upload_archive(package_id, user_secret, archive)
+}
+
+pub(crate) fn build_included_archive<P>(
+    settings: &UploadSettings,
+    base_path: &Path,
+    includes: &[P],
+) -> Result<SaveArchive, DevstoreError>
+where
+    P: AsRef<Path>,
+{
+    if includes.is_empty() {
+        return Err(DevstoreError::InvalidParam("includes"));
+    }
+    save_metadata(base_path)?;
+
+    let mut included = Vec::with_capacity(includes.len());
+    for include in includes {
+        let relative = include.as_ref();
+        let prefix = match safe_relative_path(&relative.to_string_lossy()) {
+            Some(path) if !path.as_os_str().is_empty() => path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            _ => return Err(DevstoreError::InvalidParam("includes")),
+        };
+        let path = base_path.join(relative);
+        let metadata = save_metadata(&path)?;
+        included.push((path, metadata, prefix));
+    }
+
+    let roots: Vec<SaveRoot> = included
+        .iter()
+        .map(|(path, metadata, prefix)| SaveRoot {
+            path,
+            metadata: metadata.clone(),
+            prefix: Some(prefix.clone()),
+        })
+        .collect();
+    build_save_archive(&roots, settings)
+}
+
+/// Downloads the product's cloud save and extracts it into `extract_path`,
+/// replacing the folder only once extraction has fully succeeded.
+pub fn download_save(
+    package_id: &str,
+    user_secret: &str,
+    extract_path: impl AsRef<Path>,
+) -> Result<SaveDownloadSummary, DevstoreError> {
+    download_save_streaming(package_id, user_secret, extract_path, |_, _, _| {})
+}
+
+/// [`download_save`] reporting `(phase, done, total)` as it goes: bytes
+/// received in the `Download` phase, then entries written in the `Extract`
+/// phase. The download size limit and the extract path checks apply as usual;
+/// an extract path that is a file or not writable fails before any request.
+pub fn download_save_streaming(
+    package_id: &str,
+    user_secret: &str,
+    extract_path: impl AsRef<Path>,
+    mut on_progress: impl FnMut(DevstoreProgressPhase, u64, u64),
+) -> Result<SaveDownloadSummary, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    let extract_path = validate_extract_path(extract_path.as_ref())?;
+
+    let download = fetch_save(package_id, user_secret, &mut |done, total| {
+        on_progress(DevstoreProgressPhase::Download, done, total)
+    })?;
+    let tally = extract_save_atomically(&download.file, &extract_path, &mut |done, total| {
+        on_progress(DevstoreProgressPhase::Extract, done, total)
+    })?;
+    Ok(SaveDownloadSummary {
+        files: tally.files,
+        bytes: tally.bytes,
+        extract_path,
+    })
+}
+
+/// Restores the product's cloud save as the whole of `target_dir`. The save is
+/// extracted into a fresh folder beside it, then the current `target_dir` is
+/// moved to `target_dir.bak`, replacing any older backup, and the new folder
+/// takes its place. Returns the backup path when there was a folder to back up.
+pub fn restore_save(
+    package_id: &str,
+    user_secret: &str,
+    target_dir: impl AsRef<Path>,
+) -> Result<Option<PathBuf>, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let user_secret = require(user_secret, "user_secret")?;
+    let target_dir = validate_extract_path(target_dir.as_ref())?;
+
+    let download = fetch_save(package_id, user_secret, &mut |_, _| {})?;
+    replace_with_save(&download.file, &target_dir)
+}
+
+/// Downloads the product's cloud save into a temp file, reporting bytes
+/// received to `on_download`. An empty or missing save is `NoSave`.
+fn fetch_save(
+    package_id: &str,
+    user_secret: &str,
+    on_download: &mut dyn FnMut(u64, u64),
+) -> Result<TempFile, DevstoreError> {
+    let _operation = OperationGuard::new("download");
+    cancelled_on_shutdown(download_save_archive(package_id, user_secret, on_download))
+}
+
+fn download_save_archive(
+    package_id: &str,
+    user_secret: &str,
+    on_download: &mut dyn FnMut(u64, u64),
+) -> Result<TempFile, DevstoreError> {
+    let client = build_default_client()?;
+    let concurrency = DOWNLOAD_CONCURRENCY.load(Ordering::SeqCst) as usize;
+    let mut request = client
+        .get(format!("{}cloud-saves/", api_base_url()))
+        .query(&[("user_secret", user_secret), ("product_id", package_id)]);
+    if concurrency > 1 {
+        // Asking for the first part tells whether the server serves ranges; one
+        // that does not just sends the whole save.
+        request = request.header(
+            reqwest::header::RANGE,
+            format!("bytes=0-{}", DOWNLOAD_PART_SIZE - 1),
+        );
+    }
+    let started = Instant::now();
+    let response = send_request(request)?;
+    let first_byte = Instant::now();
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND
+        || status == reqwest::StatusCode::NO_CONTENT
+        || status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+    {
+        return Err(DevstoreError::NoSave);
+    }
+    if !status.is_success() {
+        return Err(http_error(response, "Download failed"));
+    }
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let mut download = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+        download_in_parts(
+            &client,
+            &[("user_secret", user_secret), ("product_id", package_id)],
+            response,
+            concurrency,
+            on_download,
+        )?
+    } else {
+        let total = response.content_length().unwrap_or(0);
+        let body = limit_body(response, MAX_DOWNLOAD_SIZE.load(Ordering::SeqCst))?;
+        let mut body = ProgressReader::new(body, "download", total).with_listener(on_download);
+        spool_to_temp_file(&mut body, "save")?
+    };
+    let received = download
+        .file
+        .metadata()
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    if received == 0 {
+        return Err(DevstoreError::NoSave);
+    }
+    if let Some(content_type) = content_type.filter(|value| !is_archive_content_type(value)) {
+        reject_unexpected_body(&mut download.file, &content_type)?;
+    }
+    DownloadStats::record(received, started, first_byte);
+    Ok(download)
+}
+
+/// Whether a download's `Content-Type` can carry a save archive.
+fn is_archive_content_type(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    matches!(
+        essence.as_str(),
+        "application/zip"
+            | "application/x-zip-compressed"
+            | "application/zstd"
+            | "application/octet-stream"
+    )
+}
+
+/// Fails a download served as `content_type` whose body does not start like a
+/// zip or zstd archive, with the message of the JSON error envelope a
+/// misconfigured server sent along with its 200. A real archive passes so a
+/// server that mislabels saves keeps working.
+fn reject_unexpected_body(file: &mut fs::File, content_type: &str) -> Result<(), DevstoreError> {
+    let rewind = |file: &mut fs::File| {
+        file.seek(io::SeekFrom::Start(0))
+            .map_err(|e| io_failure("Error: Failed to rewind temp file", e))
+    };
+    let mut magic = Vec::new();
+    (&mut *file)
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut magic)
+        .map_err(|e| io_failure("Error: Failed to read temp file", e))?;
+    if magic.starts_with(b"PK") || magic == ZSTD_MAGIC {
+        return rewind(file).map(|_| ());
+    }
+    rewind(file)?;
+    let mut body = Vec::new();
+    (&mut *file)
+        .take(MAX_RESPONSE_SIZE.load(Ordering::SeqCst))
+        .read_to_end(&mut body)
+        .map_err(|e| io_failure("Error: Failed to read temp file", e))?;
+    Err(server_error(format!(
+        "Error: Download returned {} instead of a save: {}",
+        content_type,
+        envelope_message(&String::from_utf8_lossy(&body))
+    )))
+}
+
+/// Total size from a `Content-Range: bytes start-end/total` header.
+fn content_range_total(response: &reqwest::blocking::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split_once('/')?
+        .1
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Reads a ranged reply that must hold exactly `length` bytes.
+fn read_part(response: reqwest::blocking::Response, length: u64) -> Result<Vec<u8>, DevstoreError> {
+    let mut data = Vec::with_capacity(length as usize);
+    limit_body(response, length)?
+        .read_to_end(&mut data)
+        .map_err(|e| io_failure("Error: Failed to read download part", e))?;
+    if data.len() as u64 != length {
+        return Err(classified(
+            DEVSTORE_CODE_MALFORMED_RESPONSE,
+            format!(
+                "Error: Download part has {} bytes, expected {}",
+                data.len(),
+                length
+            ),
+        ));
+    }
+    Ok(data)
+}
+
+fn fetch_part(
+    client: &reqwest::blocking::Client,
+    query: &[(&str, &str)],
+    start: u64,
+    length: u64,
+) -> Result<(u64, Vec<u8>), DevstoreError> {
+    let response = send_request(
+        client
+            .get(format!("{}cloud-saves/", api_base_url()))
+            .query(query)
+            .header(
+                reqwest::header::RANGE,
+                format!("bytes={}-{}", start, start + length - 1),
+            ),
+    )?;
+    if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+        return Err(http_error(response, "Download failed"));
+    }
+    Ok((start, read_part(response, length)?))
+}
+
+/// Fetches the rest of a save whose first part arrived as the 206 reply
+/// `first`, `DOWNLOAD_PART_SIZE` bytes per request with up to `concurrency`
+/// requests in flight, then reassembles and verifies it in a temp file.
+fn download_in_parts(
+    client: &reqwest::blocking::Client,
+    query: &[(&str, &str)],
+    first: reqwest::blocking::Response,
+    concurrency: usize,
+    on_progress: &mut dyn FnMut(u64, u64),
+) -> Result<TempFile, DevstoreError> {
+    let total = content_range_total(&first).ok_or_else(|| {
+        classified(
+            DEVSTORE_CODE_MALFORMED_RESPONSE,
+            "Error: Ranged download has no total size",
+        )
+    })?;
+    let limit = MAX_DOWNLOAD_SIZE.load(Ordering::SeqCst);
+    if total > limit {
+        return Err(io_failure(
+            "Error: Response rejected",
+            response_too_large(limit),
+        ));
+    }
+    let expected_sha256 = first
+        .headers()
+        .get(crate::ARCHIVE_SHA256_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let mut temp = create_temp_file("save")?;
+    let mut write_part = |offset: u64, data: &[u8]| {
+        temp.file
+            .seek(io::SeekFrom::Start(offset))
+            .and_then(|_| temp.file.write_all(data))
+            .map_err(|e| io_failure("Error: Failed to write download to temp file", e))
+    };
+    let mut done = 0;
+    let mut report = |length: u64| {
+        done += length;
+        emit_progress("download", done, total);
+        on_progress(done, total);
+    };
+    report(0);
+
+    let first_length = DOWNLOAD_PART_SIZE.min(total);
+    write_part(0, &read_part(first, first_length)?)?;
+    report(first_length);
+
+    let offsets: Vec<u64> = (first_length..total)
+        .step_by(DOWNLOAD_PART_SIZE as usize)
+        .collect();
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.min(offsets.len()) {
+            let (sender, next, failed, offsets) = (sender.clone(), &next, &failed, &offsets);
+            scope.spawn(move || {
+                while !failed.load(Ordering::SeqCst) {
+                    let Some(&start) = offsets.get(next.fetch_add(1, Ordering::SeqCst)) else {
+                        break;
+                    };
+                    let length = DOWNLOAD_PART_SIZE.min(total - start);
+                    let part = fetch_part(client, query, start, length);
+                    let stop = part.is_err();
+                    if sender.send(part).is_err() || stop {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        for part in receiver {
+            let written = part.and_then(|(start, data)| {
+                write_part(start, &data)?;
+                Ok(data.len() as u64)
+            });
+            match written {
+                Ok(length) => report(length),
+                Err(error) => {
+                    failed.store(true, Ordering::SeqCst);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    verify_reassembled_archive(&mut temp.file, expected_sha256.as_deref())?;
+    Ok(temp)
+}
+
+enum VersionFetch {
+    NotModified(Option<String>),
+    Fetched(VersionLookup, Option<String>),
+}
+
+/// Requests the published version, conditionally when `etag` is given. Both
+/// outcomes carry the ETag the server sent, if any.
+fn fetch_version(package_id: &str, etag: Option<&str>) -> Result<VersionFetch, DevstoreError> {
+    let client = build_default_client()?;
+    let mut request = client
+        .get(format!("{}version-hex/", api_base_url()))
+        .query(&[("product_id", package_id)]);
+    if let Some(etag) = etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    let response = send_request(request)?;
+
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(VersionFetch::NotModified(etag));
+    }
+    let text = response_text(response, "Request failed")?;
+    let lookup = match serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|json| json.get("version").cloned())
+    {
+        Some(version) => VersionLookup::Found(version),
+        None => VersionLookup::Unrecognized(text),
+    };
+    Ok(VersionFetch::Fetched(lookup, etag))
+}
+
+pub(crate) fn lookup_version(package_id: &str) -> Result<VersionLookup, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    match fetch_version(package_id, None)? {
+        VersionFetch::Fetched(lookup, _) => Ok(lookup),
+        VersionFetch::NotModified(_) => Err(server_error(
+            "Error: Unexpected 304 response to a version request",
+        )),
+    }
+}
+
+/// `lookup_version` through version_cache.json under the pref path. A version
+/// younger than the TTL set with `set_version_cache_ttl` is returned without a
+/// request unless `force` is set; otherwise the cached entry is revalidated
+/// with its ETag. With a TTL of 0 the cache is not used at all.
+pub(crate) fn lookup_version_cached(
+    package_id: &str,
+    force: bool,
+) -> Result<VersionLookup, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let ttl = VERSION_CACHE_TTL.load(Ordering::SeqCst);
+    if ttl == 0 {
+        return lookup_version(package_id);
+    }
+
+    let cache_path = get_pref_path().join("version_cache.json");
+    let mut cache: BTreeMap<String, VersionCacheEntry> = fs::read(&cache_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default();
+    let now = unix_now();
+    let cached = cache.remove(package_id);
+    if !force
+        && let Some(entry) = &cached
+        && now.saturating_sub(entry.fetched_at) < ttl.min(i64::MAX as u64) as i64
+    {
+        return Ok(VersionLookup::Found(entry.version.clone()));
+    }
+
+    let etag = cached.as_ref().and_then(|entry| entry.etag.as_deref());
+    let entry = match (fetch_version(package_id, etag)?, cached) {
+        (VersionFetch::NotModified(etag), Some(entry)) => VersionCacheEntry {
+            etag: etag.or(entry.etag),
+            fetched_at: now,
+            ..entry
+        },
+        (VersionFetch::Fetched(VersionLookup::Found(version), etag), _) => VersionCacheEntry {
+            version,
+            etag,
+            fetched_at: now,
+        },
+        (VersionFetch::Fetched(lookup, _), _) => return Ok(lookup),
+        (VersionFetch::NotModified(_), None) => {
+            return Err(server_error(
+                "Error: Unexpected 304 response to a version request",
+            ));
+        }
+    };
+    let version = entry.version.clone();
+    cache.insert(package_id.to_string(), entry);
+    if let Ok(data) = serde_json::to_vec_pretty(&cache) {
+        let _ = fs::write(&cache_path, data);
+    }
+    Ok(VersionLookup::Found(version))
+}
+
+/// Returns the `version` field the store publishes for the product.
+pub fn get_version(package_id: &str) -> Result<Value, DevstoreError> {
+    match lookup_version(package_id)? {
+        VersionLookup::Found(version) => Ok(version),
+        VersionLookup::Unrecognized(text) => Err(server_error(format!(
+            "Error: Response has no version: {}",
+            text
+        ))),
+    }
+}
+
+/// Compares the published version with `current_version_hex` and, when it is
+/// newer, fetches the changelog and patch size from the product metadata in
+/// the same call so an update prompt needs no second round-trip. Both are
+/// cached in changelog_cache.json, keyed by the version they describe.
+pub fn get_update_info(
+    package_id: &str,
+    current_version_hex: &str,
+) -> Result<UpdateInfo, DevstoreError> {
+    let package_id = normalize_product_id(package_id, "package_id")?;
+    let latest_version = match lookup_version_cached(package_id, false)? {
+        VersionLookup::Found(version) => version,
+        VersionLookup::Unrecognized(text) => {
+            return Err(server_error(format!(
+                "Error: Response has no version: {}",
+                text
+            )));
+        }
+    };
+    if !version_is_newer(&latest_version, current_version_hex) {
+        return Ok(UpdateInfo {
+            update_available: false,
+            latest_version,
+            changelog: None,
+            size: None,
+        });
+    }
+
+    let cache_path = get_pref_path().join(CHANGELOG_CACHE_FILE);
+    let mut cache: BTreeMap<String, ChangelogCacheEntry> = fs::read(&cache_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default();
+    let entry = match cache.remove(package_id) {
+        Some(entry) if entry.version == latest_version => entry,
+        _ => fetch_changelog(package_id, &latest_version)?,
+    };
+    let info = UpdateInfo {
+        update_available: true,
+        latest_version,
+        changelog: entry.changelog.clone(),
+        size: entry.size,
+    };
+    cache.insert(package_id.to_string(), entry);
+    if let Ok(data) = serde_json::to_vec_pretty(&cache) {
+        let _ = fs::write(&cache_path, data);
+    }
+    Ok(info)
+}
+
+/// Reads `changelog` and `patch_size` from the product metadata; either may be
+/// missing.
+fn fetch_changelog(
+    package_id: &str,
+    version: &Value,
+) -> Result<ChangelogCacheEntry, DevstoreError> {
+    let client = build_default_client()?;
+    let response = send_request(
+        client
+            .get(format!("{}product-metadata/", api_base_url()))
+            .query(&[("product_id", package_id)]),
+    )?;
+    let text = response_text(response, "Error: Metadata request failed")?;
+    let metadata: Value = serde_json::from_str(&text).map_err(|_| {
+        classified(
+            DEVSTORE_CODE_MALFORMED_RESPONSE,
+            format!("Error: Malformed product metadata: {}", text),
+        )
+    })?;
+    Ok(ChangelogCacheEntry {
+        version: version.clone(),
+        changelog: metadata
+            .get("changelog")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        size: metadata.get("patch_size").and_then(Value::as_u64),
+    })
+}
+
+fn classified(code: u32, message: impl Into<String>) -> DevstoreError {
+    DevstoreError::Response {
+        code,
+        message: message.into(),
+    }
+}
+
+/// Failures carry `DEVSTORE_CODE_AUTH_INVALID` when the server rejects the
+/// secret (HTTP 401/403 or an `"error"` status), `DEVSTORE_CODE_SERVER_ERROR`
+/// for other failed requests and `DEVSTORE_CODE_MALFORMED_RESPONSE` when the
+/// reply cannot be understood.
+pub fn get_current_username(user_secret: &str) -> Result<String, DevstoreError> {
+    let user_secret = require(user_secret, "user_secret")?;
+
+    let client = build_default_client()?;
+    let response = send_request(
+        client
+            .post(format!("{}get-username-by-secret/", api_base_url()))
+            .form(&[("user_secret", user_secret)]),
+    )?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let text = error_message(response).unwrap_or_else(|| "No response message".to_string());
+        let code = match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+                DEVSTORE_CODE_AUTH_INVALID
+            }
+            _ => DEVSTORE_CODE_SERVER_ERROR,
+        };
+        return Err(classified(
+            code,
+            format!(
+                "Error: Request failed (status {}): {}",
+                status.as_u16(),
+                text
+            ),
+        ));
+    }
+
+    let text = read_response_text(response)?;
+    let json: Value = serde_json::from_str(&text).map_err(|e| {
+        classified(
+            DEVSTORE_CODE_MALFORMED_RESPONSE,
+            format!("Error: Failed to parse response JSON: {}", e),
+        )
+    })?;
+    match json.get("status").and_then(Value::as_str) {
+        Some("success") => match json.get("username").and_then(Value::as_str) {
+            Some(username) => Ok(username.to_string()),
+            None => Err(classified(
+                DEVSTORE_CODE_MALFORMED_RESPONSE,
+                "Error: Username missing in response",
+            )),
+        },
+        Some("error") => {
+            let msg = json
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("Unknown error");
+            Err(classified(
+                DEVSTORE_CODE_AUTH_INVALID,
+                format!("Error: Server error: {}", msg),
+            ))
+        }
+        Some(other) => Err(classified(
+            DEVSTORE_CODE_MALFORMED_RESPONSE,
+            format!("Error: Unexpected status in response: {}", other),
+        )),
+        None => Err(classified(
+            DEVSTORE_CODE_MALFORMED_RESPONSE,
+            "Error: Missing status in response",
+        )),
+    }
+}
+
+/// Downloads the product's icon into the icon cache under the pref path and
+/// returns the cached file, or `out_path` after copying the icon there. The
+/// server's Cache-Control, ETag and Last-Modified headers are honoured: a fresh
+/// icon is reused without any request and a stale one is revalidated.
+pub fn fetch_app_icon(product_id: &str, out_path: Option<&Path>) -> Result<PathBuf, DevstoreError> {
+    let product_id = normalize_product_id(product_id, "product_id")?;
+
+    let icon_dir = get_pref_path().join("icons");
+    fs::create_dir_all(&icon_dir)
+        .map_err(|e| io_failure("Error: Failed to create icon cache folder", e))?;
+    let entry_path = icon_dir.join(format!("{}.json", product_id));
+    let cached = fs::read(&entry_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<IconCacheEntry>(&data).ok())
+        .filter(|entry| icon_dir.join(&entry.file).is_file());
+
+    let now = unix_now();
+    let entry = match cached {
+        Some(entry) if entry.expires_at > now => entry,
+        cached => {
+            let entry = refresh_app_icon(product_id, &icon_dir, cached, now)?;
+            let data = serde_json::to_vec(&entry)
+                .map_err(|e| server_error(format!("Error: Failed to encode icon cache: {}", e)))?;
+            fs::write(&entry_path, data)
+                .map_err(|e| io_failure("Error: Failed to write icon cache", e))?;
+            entry
+        }
+    };
+
+    let icon_path = icon_dir.join(&entry.file);
+    match out_path {
+        Some(out_path) => {
+            fs::copy(&icon_path, out_path)
+                .map_err(|e| io_failure("Error: Failed to copy icon", e))?;
+            Ok(out_path.to_path_buf())
+        }
+        None => Ok(icon_path),
+    }
+}
+
+fn refresh_app_icon(
+    product_id: &str,
+    icon_dir: &Path,
+    cached: Option<IconCacheEntry>,
+    now: i64,
+) -> Result<IconCacheEntry, DevstoreError> {
+    let client = build_default_client()?;
+    let response = send_request(
+        client
+            .get(format!("{}product-metadata/", api_base_url()))
+            .query(&[("product_id", product_id)]),
+    )?;
+    let text = response_text(response, "Error: Metadata request failed")?;
+    let icon_url = serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|json| json.get("icon_url")?.as_str().map(str::to_string))
+        .ok_or_else(|| server_error("Error: Product metadata has no icon_url"))?;
+    let icon_url = reqwest::Url::parse(&api_base_url())
+        .and_then(|base| base.join(&icon_url))
+        .map_err(|_| server_error(format!("Error: Invalid icon URL: {}", icon_url)))?;
+
+    let cached = cached.filter(|entry| entry.icon_url == icon_url.as_str());
+    let mut request = client.get(icon_url.clone());
+    if let Some(entry) = &cached {
+        if let Some(etag) = &entry.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = send_request(request)?;
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let max_age = header(reqwest::header::CACHE_CONTROL)
+        .map(|value| cache_max_age(&value))
+        .unwrap_or(0);
+    let expires_at = now.saturating_add(max_age.min(i64::MAX as u64) as i64);
+    let etag = header(reqwest::header::ETAG);
+    let last_modified = header(reqwest::header::LAST_MODIFIED);
+    let content_type = header(reqwest::header::CONTENT_TYPE);
+
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED
+        && let Some(entry) = cached
+    {
+        return Ok(IconCacheEntry {
+            etag: etag.or(entry.etag),
+            last_modified: last_modified.or(entry.last_modified),
+            expires_at,
+            ..entry
+        });
+    }
+    if !response.status().is_success() {
+        return Err(http_error(response, "Error: Icon download failed"));
+    }
+    let mut bytes = Vec::new();
+    limit_body(response, MAX_DOWNLOAD_SIZE.load(Ordering::SeqCst))?
+        .read_to_end(&mut bytes)
+        .map_err(|e| io_failure("Error: Failed to read icon", e))?;
+
+    let extension = match content_type.as_deref() {
+        Some("image/png") => "png",
+        Some("image/jpeg") => "jpg",
+        Some("image/x-icon") | Some("image/vnd.microsoft.icon") => "ico",
+        _ => Path::new(icon_url.path())
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .filter(|extension| extension.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or("png"),
+    };
+    let file = format!("{}.{}", product_id, extension.to_ascii_lowercase());
+    fs::write(icon_dir.join(&file), &bytes)
+        .map_err(|e| io_failure("Error: Failed to write icon", e))?;
+    if let Some(entry) = cached
+        && entry.file != file
+    {
+        let _ = fs::remove_file(icon_dir.join(entry.file));
+    }
+    Ok(IconCacheEntry {
+        icon_url: icon_url.to_string(),
+        file,
+        etag,
+        last_modified,
+        expires_at,
+    })
+}
+
+/// Lists the products in the user's library. A rejected secret is reported as
+/// `Http` with status 401 or 403.
+pub fn list_owned_products(user_secret: &str) -> Result<Vec<OwnedProduct>, DevstoreError> {
+    let user_secret = require(user_secret, "user_secret")?;
+
+    let client = build_default_client()?;
+    let response = send_request(
+        client
+            .post(format!("{}owned-products/", api_base_url()))
+            .form(&[("user_secret", user_secret)]),
+    )?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(DevstoreError::Http {
+            status: status.as_u16(),
+            message: "Error: Authentication failed, the user secret was rejected.".to_string(),
+        });
+    }
+    let text = response_text(response, "Error: Request failed")?;
+    parse_owned_products(&text)
+}
+
+/// Lists the user's cloud saves, for one product or across all of them when
+/// `product_id` is `None`, newest first.
+pub fn list_cloud_saves(
+    user_secret: &str,
+    product_id: Option<&str>,
+) -> Result<Vec<CloudSave>, DevstoreError> {
+    let user_secret = require(user_secret, "user_secret")?;
+    let mut query = vec![("user_secret", user_secret)];
+    if let Some(product_id) = product_id {
+        query.push((
+            "product_id",
+            normalize_product_id(product_id, "product_id")?,
+        ));
+    }
+
+    let client = build_default_client()?;
+    let response = send_request(
+        client
+            .get(format!("{}cloud-saves/list/", api_base_url()))
+            .query(&query),
+    )?;
+    let text = response_text(response, "Error: Request failed")?;
+
+    let parsed = serde_json::from_str::<CloudSavesResponse>(&text)
+        .map_err(|e| server_error(format!("Error: Failed to parse cloud save list: {}", e)))?;
+    if parsed.status != "success" {
+        return Err(server_error(format!(
+            "Error: Server error: {}",
+            parsed.message.as_deref().unwrap_or("Unknown error")
+        )));
+    }
+    let mut saves = parsed.saves;
+    saves.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(b.id.cmp(&a.id)));
+    Ok(saves)
+}
+
+/// Deletes all but the newest `keep_count` cloud saves. Deleting every save
+/// (`keep_count == 0`) is refused with `InvalidParam` unless `force` is set.
+pub fn prune_cloud_saves(
+    user_secret: &str,
+    product_id: Option<&str>,
+    keep_count: usize,
+    force: bool,
+) -> Result<PruneReport, DevstoreError> {
+    if keep_count == 0 && !force {
+        return Err(DevstoreError::InvalidParam("keep_count"));
+    }
+    let saves = list_cloud_saves(user_secret, product_id)?;
+
+    let client = build_default_client()?;
+    let mut report = PruneReport::default();
+    for save in saves.iter().skip(keep_count) {
+        let id = save.id.to_string();
+        let response = send_request(
+            client
+                .post(format!("{}cloud-saves/delete/", api_base_url()))
+                .form(&[("user_secret", user_secret), ("save_id", id.as_str())]),
+        )?;
+        if !response.status().is_success() {
+            let label = format!(
+                "Error: Removed {} save(s) ({} bytes) before delete of save {} failed",
+                report.removed, report.bytes_freed, save.id
+            );
+            return Err(http_error(response, &label));
+        }
+        report.removed += 1;
+        report.bytes_freed += save.size_bytes;
+    }
+    Ok(report)
+}
+
+/// Asks the status endpoint whether the store is reachable. Redirects are
+/// followed, up to `STATUS_CHECK_MAX_REDIRECTS` hops.
+pub fn is_devstore_online() -> Result<OnlineStatus, DevstoreError> {
+    check_online(
+        &client_builder()
+            .redirect(reqwest::redirect::Policy::limited(
+                STATUS_CHECK_MAX_REDIRECTS,
+            ))
+            .build()?,
+    )
+}
+
+fn check_online(client: &reqwest::blocking::Client) -> Result<OnlineStatus, DevstoreError> {
+    let response = send_request(client.get(format!("{}status-check", api_base_url())))?;
+    let status = response.status();
+    Ok(if status.is_success() {
+        OnlineStatus::Online
+    } else if status == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+        OnlineStatus::Maintenance
+    } else if status.is_client_error() {
+        OnlineStatus::Misconfigured(status.as_u16())
+    } else {
+        OnlineStatus::Unexpected(status.as_u16())
+    })
+}
+
+#[cfg(feature = "keyring")]
+static KEYRING_ENTRY: once_cell::sync::OnceCell<keyring::Entry> = once_cell::sync::OnceCell::new();
+
+#[cfg(feature = "keyring")]
+fn keyring_entry() -> Result<&'static keyring::Entry, DevstoreError> {
+    KEYRING_ENTRY
+        .get_or_try_init(|| keyring::Entry::new("devstoreSDK", "user_secret"))
+        .map_err(keystore_error)
+}
+
+#[cfg(feature = "keyring")]
+fn keystore_error(error: keyring::Error) -> DevstoreError {
+    DevstoreError::Keystore {
+        message: format!(
+            "Error: OS keystore unavailable, pass user_secret explicitly: {}",
+            error
+        ),
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keystore_disabled() -> DevstoreError {
+    DevstoreError::Keystore {
+        message: "Error: This build has no OS keystore support, pass user_secret explicitly"
+            .to_string(),
+    }
+}
+
+/// Saves the user secret in the OS keystore (Keychain, Credential Manager or
+/// Secret Service) so the C exports accept a NULL `user_secret` afterwards.
+pub fn store_user_secret(user_secret: &str) -> Result<(), DevstoreError> {
+    let user_secret = require(user_secret, "user_secret")?;
+    #[cfg(feature = "keyring")]
+    {
+        keyring_entry()?
+            .set_password(user_secret)
+            .map_err(keystore_error)
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        let _ = user_secret;
+        Err(keystore_disabled())
+    }
+}
+
+/// Returns the stored user secret, or `None` when nothing has been stored.
+pub fn stored_user_secret() -> Result<Option<String>, DevstoreError> {
+    #[cfg(feature = "keyring")]
+    {
+        match keyring_entry()?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(error) => Err(keystore_error(error)),
+        }
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        Err(keystore_disabled())
+    }
+}
+
+/// Removes the stored user secret. Returns whether one was stored.
+pub fn clear_user_secret() -> Result<bool, DevstoreError> {
+    #[cfg(feature = "keyring")]
+    {
+        match keyring_entry()?.delete_credential() {
+            Ok(()) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(error) => Err(keystore_error(error)),
+        }
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        Err(keystore_disabled())
+    }
+}
+
+/// Polls the status endpoint with exponential backoff until the store reports
+/// online or `timeout` elapses. On timeout the last answer is returned: the
+/// status the server gave, or the error of the last failed request.
+/// `request_shutdown` makes a pending wait return `Cancelled`.
+pub fn wait_until_online(timeout: Duration) -> Result<OnlineStatus, DevstoreError> {
+    let client = client_builder()
+        .connect_timeout(DISCORD_CONNECT_TIMEOUT)
+        .timeout(DISCORD_REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::limited(
+            STATUS_CHECK_MAX_REDIRECTS,
+        ))
+        .build()?;
+    let deadline = Instant::now() + timeout;
+    let mut delay = WAIT_ONLINE_INITIAL_DELAY;
+    loop {
+        if shutdown_requested() {
+            return Err(DevstoreError::Cancelled);
+        }
+        let result = check_online(&client);
+        if matches!(result, Ok(OnlineStatus::Online)) {
+            return result;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return result;
+        }
+        let wake_at = deadline.min(now + delay);
+        while Instant::now() < wake_at {
+            if shutdown_requested() {
+                return Err(DevstoreError::Cancelled);
+            }
+            std::thread::sleep(SHUTDOWN_POLL_INTERVAL.min(wake_at - Instant::now()));
+        }
+        delay = (delay * 2).min(WAIT_ONLINE_MAX_DELAY);
+    }
+}